@@ -1,5 +1,8 @@
 use core::ffi::c_void;
 use std::convert::TryFrom;
+use std::marker::PhantomData;
+use std::mem;
+use std::mem::MaybeUninit;
 use std::path::Path;
 
 use bitflags::bitflags;
@@ -59,6 +62,41 @@ impl OpenMap {
     pub fn set_inner_map_fd(&mut self, inner: &Map) {
         unsafe { libbpf_sys::bpf_map__set_inner_map_fd(self.ptr, inner.fd()) };
     }
+
+    /// Sets the BTF type id of this map's key, used by the kernel for pretty-printing and type
+    /// checking (e.g. via `bpftool map dump`).
+    pub fn set_btf_key_type_id(&mut self, type_id: u32) -> Result<()> {
+        let ret = unsafe { libbpf_sys::bpf_map__set_btf_key_type_id(self.ptr, type_id) };
+        if ret != 0 {
+            // Error code is returned negative, flip to positive to match errno
+            Err(Error::System(-ret))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Sets the BTF type id of this map's value, used by the kernel for pretty-printing and type
+    /// checking (e.g. via `bpftool map dump`).
+    pub fn set_btf_value_type_id(&mut self, type_id: u32) -> Result<()> {
+        let ret = unsafe { libbpf_sys::bpf_map__set_btf_value_type_id(self.ptr, type_id) };
+        if ret != 0 {
+            // Error code is returned negative, flip to positive to match errno
+            Err(Error::System(-ret))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Options for [`Map::create()`].
+#[derive(Clone, Default)]
+pub struct MapCreateOpts {
+    pub map_flags: u32,
+    pub numa_node: u32,
+    pub map_ifindex: u32,
+    /// Fd of the inner map template. Required for [`MapType::ArrayOfMaps`] and
+    /// [`MapType::HashOfMaps`].
+    pub inner_map_fd: Option<i32>,
 }
 
 /// Represents a created map.
@@ -93,6 +131,60 @@ impl Map {
         }
     }
 
+    /// Creates a new map from scratch, independent of any BPF object file, by calling
+    /// `bpf_map_create` directly.
+    ///
+    /// This is useful for building inner maps for map-in-map types, scratch maps, or maps meant
+    /// to be shared across processes, none of which need to be described in a BPF object's
+    /// `.maps` section.
+    ///
+    /// Maps created this way have no backing `bpf_map` pointer, so [`Map::pin()`] and
+    /// [`Map::unpin()`] are not available on them.
+    pub fn create(
+        map_type: MapType,
+        name: &str,
+        key_size: u32,
+        value_size: u32,
+        max_entries: u32,
+        opts: &MapCreateOpts,
+    ) -> Result<Self> {
+        let name_c = std::ffi::CString::new(name)
+            .map_err(|e| Error::InvalidInput(format!("invalid map name: {}", e)))?;
+
+        let create_opts = libbpf_sys::bpf_map_create_opts {
+            sz: mem::size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
+            map_flags: opts.map_flags,
+            numa_node: opts.numa_node,
+            map_ifindex: opts.map_ifindex,
+            inner_map_fd: opts.inner_map_fd.unwrap_or(0) as u32,
+            ..Default::default()
+        };
+
+        let ret = unsafe {
+            libbpf_sys::bpf_map_create(
+                map_type.clone() as u32,
+                name_c.as_ptr(),
+                key_size,
+                value_size,
+                max_entries,
+                &create_opts,
+            )
+        };
+
+        if ret < 0 {
+            return Err(Error::System(-ret));
+        }
+
+        Ok(Map::new(
+            ret,
+            name.to_string(),
+            map_type as u32,
+            key_size,
+            value_size,
+            std::ptr::null_mut(),
+        ))
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -119,9 +211,43 @@ impl Map {
         self.value_size
     }
 
+    /// BTF type id of this map's key, as assigned by the kernel from the `.maps` section's BTF
+    /// info. Returns `None` if the map has no associated `bpf_map` (e.g. one created via
+    /// [`Map::create()`]) or if no BTF key type was set.
+    pub fn btf_key_type_id(&self) -> Option<u32> {
+        if self.ptr.is_null() {
+            return None;
+        }
+
+        match unsafe { libbpf_sys::bpf_map__btf_key_type_id(self.ptr) } {
+            0 => None,
+            type_id => Some(type_id),
+        }
+    }
+
+    /// BTF type id of this map's value, as assigned by the kernel from the `.maps` section's BTF
+    /// info. Returns `None` if the map has no associated `bpf_map` (e.g. one created via
+    /// [`Map::create()`]) or if no BTF value type was set.
+    pub fn btf_value_type_id(&self) -> Option<u32> {
+        if self.ptr.is_null() {
+            return None;
+        }
+
+        match unsafe { libbpf_sys::bpf_map__btf_value_type_id(self.ptr) } {
+            0 => None,
+            type_id => Some(type_id),
+        }
+    }
+
     /// [Pin](https://facebookmicrosites.github.io/bpf/blog/2018/08/31/object-lifetime.html#bpffs)
     /// this map to bpffs.
     pub fn pin<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        if self.ptr.is_null() {
+            return Err(Error::InvalidInput(
+                "cannot pin a map that wasn't loaded from a BPF object".to_string(),
+            ));
+        }
+
         let path_c = util::path_to_cstring(path)?;
         let path_ptr = path_c.as_ptr();
 
@@ -137,6 +263,12 @@ impl Map {
     /// [Unpin](https://facebookmicrosites.github.io/bpf/blog/2018/08/31/object-lifetime.html#bpffs)
     /// from bpffs
     pub fn unpin<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        if self.ptr.is_null() {
+            return Err(Error::InvalidInput(
+                "cannot unpin a map that wasn't loaded from a BPF object".to_string(),
+            ));
+        }
+
         let path_c = util::path_to_cstring(path)?;
         let path_ptr = path_c.as_ptr();
 
@@ -152,7 +284,16 @@ impl Map {
     /// Returns map value as `Vec` of `u8`.
     ///
     /// `key` must have exactly [`Map::key_size()`] elements.
+    ///
+    /// This errors out if the map is a per-CPU map; use [`Map::lookup_percpu()`] instead, since
+    /// a single `value_size()`-sized buffer is not enough to hold one value per CPU.
     pub fn lookup(&self, key: &[u8], flags: MapFlags) -> Result<Option<Vec<u8>>> {
+        if self.map_type().is_percpu() {
+            return Err(Error::InvalidInput(
+                "lookup() called on a per-CPU map, use lookup_percpu() instead".to_string(),
+            ));
+        }
+
         if key.len() != self.key_size() as usize {
             return Err(Error::InvalidInput(format!(
                 "key_size {} != {}",
@@ -190,7 +331,7 @@ impl Map {
     /// Deletes an element from the map.
     ///
     /// `key` must have exactly [`Map::key_size()`] elements.
-    pub fn delete(&mut self, key: &[u8]) -> Result<()> {
+    pub fn delete(&self, key: &[u8]) -> Result<()> {
         if key.len() != self.key_size() as usize {
             return Err(Error::InvalidInput(format!(
                 "key_size {} != {}",
@@ -216,7 +357,7 @@ impl Map {
     /// and [`MapType::Stack`].
     ///
     /// `key` must have exactly [`Map::key_size()`] elements.
-    pub fn lookup_and_delete(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+    pub fn lookup_and_delete(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
         if key.len() != self.key_size() as usize {
             return Err(Error::InvalidInput(format!(
                 "key_size {} != {}",
@@ -254,7 +395,16 @@ impl Map {
     ///
     /// `key` must have exactly [`Map::key_size()`] elements. `value` must have exatly
     /// [`Map::value_size()`] elements.
-    pub fn update(&mut self, key: &[u8], value: &[u8], flags: MapFlags) -> Result<()> {
+    ///
+    /// This errors out if the map is a per-CPU map; use [`Map::update_percpu()`] instead, since
+    /// a single `value_size()`-sized buffer holds only one CPU's value.
+    pub fn update(&self, key: &[u8], value: &[u8], flags: MapFlags) -> Result<()> {
+        if self.map_type().is_percpu() {
+            return Err(Error::InvalidInput(
+                "update() called on a per-CPU map, use update_percpu() instead".to_string(),
+            ));
+        }
+
         if key.len() != self.key_size() as usize {
             return Err(Error::InvalidInput(format!(
                 "key_size {} != {}",
@@ -286,6 +436,444 @@ impl Map {
             Err(Error::System(errno::errno()))
         }
     }
+
+    /// Returns one value per CPU as a `Vec`, for a per-CPU map type.
+    ///
+    /// `key` must have exactly [`Map::key_size()`] elements.
+    ///
+    /// The kernel rounds each per-CPU slot up to an 8-byte boundary, so the syscall buffer can be
+    /// larger than `value_size() * num_cpus`; this unpacks it back into one `value_size()`-length
+    /// `Vec<u8>` per possible CPU.
+    pub fn lookup_percpu(&self, key: &[u8], flags: MapFlags) -> Result<Option<Vec<Vec<u8>>>> {
+        if !self.map_type().is_percpu() {
+            return Err(Error::InvalidInput(
+                "lookup_percpu() called on a non-per-CPU map, use lookup() instead".to_string(),
+            ));
+        }
+
+        if key.len() != self.key_size() as usize {
+            return Err(Error::InvalidInput(format!(
+                "key_size {} != {}",
+                key.len(),
+                self.key_size()
+            )));
+        };
+
+        let num_cpus = num_possible_cpus()?;
+        let percpu_size = roundup(self.value_size() as usize, 8);
+        let mut out: Vec<u8> = Vec::with_capacity(percpu_size * num_cpus);
+
+        let ret = unsafe {
+            libbpf_sys::bpf_map_lookup_elem_flags(
+                self.fd as i32,
+                key.as_ptr() as *const c_void,
+                out.as_mut_ptr() as *mut c_void,
+                flags.bits,
+            )
+        };
+
+        if ret == 0 {
+            unsafe {
+                out.set_len(percpu_size * num_cpus);
+            }
+
+            Ok(Some(
+                out.chunks(percpu_size)
+                    .map(|chunk| chunk[..self.value_size() as usize].to_vec())
+                    .collect(),
+            ))
+        } else {
+            let errno = errno::errno();
+            if errno::Errno::from_i32(errno) == errno::Errno::ENOENT {
+                Ok(None)
+            } else {
+                Err(Error::System(errno))
+            }
+        }
+    }
+
+    /// Update an element in a per-CPU map, setting the value for every CPU at once.
+    ///
+    /// `key` must have exactly [`Map::key_size()`] elements. `values` must have exactly one
+    /// `value_size()`-length entry per possible CPU, as returned by [`num_possible_cpus()`].
+    pub fn update_percpu(&mut self, key: &[u8], values: &[Vec<u8>], flags: MapFlags) -> Result<()> {
+        if !self.map_type().is_percpu() {
+            return Err(Error::InvalidInput(
+                "update_percpu() called on a non-per-CPU map, use update() instead".to_string(),
+            ));
+        }
+
+        if key.len() != self.key_size() as usize {
+            return Err(Error::InvalidInput(format!(
+                "key_size {} != {}",
+                key.len(),
+                self.key_size()
+            )));
+        };
+
+        let num_cpus = num_possible_cpus()?;
+        if values.len() != num_cpus {
+            return Err(Error::InvalidInput(format!(
+                "number of values {} != number of cpus {}",
+                values.len(),
+                num_cpus
+            )));
+        }
+
+        let percpu_size = roundup(self.value_size() as usize, 8);
+        let mut value_buf = vec![0u8; percpu_size * num_cpus];
+
+        for (i, value) in values.iter().enumerate() {
+            if value.len() != self.value_size() as usize {
+                return Err(Error::InvalidInput(format!(
+                    "value_size {} != {}",
+                    value.len(),
+                    self.value_size()
+                )));
+            }
+
+            let start = i * percpu_size;
+            value_buf[start..start + value.len()].copy_from_slice(value);
+        }
+
+        let ret = unsafe {
+            libbpf_sys::bpf_map_update_elem(
+                self.fd as i32,
+                key.as_ptr() as *const c_void,
+                value_buf.as_ptr() as *const c_void,
+                flags.bits,
+            )
+        };
+
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(Error::System(errno::errno()))
+        }
+    }
+
+    /// Returns an iterator over all keys currently in the map, built on
+    /// `bpf_map_get_next_key`.
+    ///
+    /// Keys are not guaranteed to be returned in any particular order, and concurrent
+    /// modification of the map may cause keys to be skipped or repeated. The iterator stops on
+    /// `ENOENT` (no more keys); any other error is yielded as `Some(Err(_))` rather than being
+    /// mistaken for the end of the map.
+    pub fn keys(&self) -> MapKeyIter<'_> {
+        MapKeyIter {
+            map: self,
+            prev: None,
+            done: false,
+        }
+    }
+
+    /// Returns an iterator over all key/value pairs currently in the map, pairing each key
+    /// yielded by [`Map::keys()`] with its current value via [`Map::lookup()`].
+    ///
+    /// As with [`Map::keys()`], a lookup failure other than the key having raced a delete (e.g.
+    /// calling this on a per-CPU map, where [`Map::lookup()`] always errors) is surfaced as
+    /// `Some(Err(_))` instead of silently ending iteration.
+    pub fn iter(&self) -> MapIter<'_> {
+        MapIter { keys: self.keys() }
+    }
+
+    /// Returns a fresh cursor to drive repeated calls to [`Map::lookup_batch()`].
+    pub fn batch_cursor(&self) -> MapBatchCursor<'_> {
+        MapBatchCursor {
+            map: self,
+            batch: vec![0u8; self.key_size() as usize],
+            started: false,
+            done: false,
+        }
+    }
+
+    /// Looks up up to `batch_size` key/value pairs in a single syscall, resuming from `cursor`.
+    ///
+    /// Returns an empty `Vec` once the map has been fully walked; check
+    /// [`MapBatchCursor::is_done()`] to tell "no more entries" apart from "none matched this
+    /// call".
+    pub fn lookup_batch(
+        &self,
+        cursor: &mut MapBatchCursor<'_>,
+        batch_size: u32,
+        flags: MapFlags,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        if !std::ptr::eq(self, cursor.map) {
+            return Err(Error::InvalidInput(
+                "cursor was created from a different map".to_string(),
+            ));
+        }
+
+        if cursor.done {
+            return Ok(Vec::new());
+        }
+
+        let mut keys = vec![0u8; self.key_size() as usize * batch_size as usize];
+        let mut values = vec![0u8; self.value_size() as usize * batch_size as usize];
+        let mut out_batch = vec![0u8; self.key_size() as usize];
+        let mut count = batch_size;
+
+        let opts = libbpf_sys::bpf_map_batch_opts {
+            sz: mem::size_of::<libbpf_sys::bpf_map_batch_opts>() as libbpf_sys::size_t,
+            elem_flags: flags.bits,
+            flags: 0,
+        };
+
+        let in_batch_ptr = if cursor.started {
+            cursor.batch.as_mut_ptr() as *mut c_void
+        } else {
+            std::ptr::null_mut()
+        };
+
+        let ret = unsafe {
+            libbpf_sys::bpf_map_lookup_batch(
+                self.fd,
+                in_batch_ptr,
+                out_batch.as_mut_ptr() as *mut c_void,
+                keys.as_mut_ptr() as *mut c_void,
+                values.as_mut_ptr() as *mut c_void,
+                &mut count,
+                &opts,
+            )
+        };
+
+        let entries = (0..count as usize)
+            .map(|i| {
+                let key_start = i * self.key_size() as usize;
+                let value_start = i * self.value_size() as usize;
+                (
+                    keys[key_start..key_start + self.key_size() as usize].to_vec(),
+                    values[value_start..value_start + self.value_size() as usize].to_vec(),
+                )
+            })
+            .collect();
+
+        if ret == 0 {
+            cursor.started = true;
+            cursor.batch = out_batch;
+            Ok(entries)
+        } else {
+            let errno = -ret;
+            if errno::Errno::from_i32(errno) == errno::Errno::ENOENT {
+                // The kernel has no more batches for us, but the walk completed successfully up
+                // to this point: keep the entries it did return and mark the cursor done rather
+                // than advancing it to a bogus position.
+                cursor.done = true;
+                Ok(entries)
+            } else {
+                Err(Error::System(errno))
+            }
+        }
+    }
+
+    /// Updates many key/value pairs in a single syscall.
+    ///
+    /// `keys` and `values` must have the same length, with each `keys[i]`/`values[i]` having
+    /// exactly [`Map::key_size()`]/[`Map::value_size()`] elements.
+    pub fn update_batch(
+        &mut self,
+        keys: &[Vec<u8>],
+        values: &[Vec<u8>],
+        flags: MapFlags,
+    ) -> Result<()> {
+        if keys.len() != values.len() {
+            return Err(Error::InvalidInput(format!(
+                "number of keys {} != number of values {}",
+                keys.len(),
+                values.len()
+            )));
+        }
+
+        let mut key_buf = Vec::with_capacity(keys.len() * self.key_size() as usize);
+        for key in keys {
+            if key.len() != self.key_size() as usize {
+                return Err(Error::InvalidInput(format!(
+                    "key_size {} != {}",
+                    key.len(),
+                    self.key_size()
+                )));
+            }
+            key_buf.extend_from_slice(key);
+        }
+
+        let mut value_buf = Vec::with_capacity(values.len() * self.value_size() as usize);
+        for value in values {
+            if value.len() != self.value_size() as usize {
+                return Err(Error::InvalidInput(format!(
+                    "value_size {} != {}",
+                    value.len(),
+                    self.value_size()
+                )));
+            }
+            value_buf.extend_from_slice(value);
+        }
+
+        let mut count = keys.len() as u32;
+        let opts = libbpf_sys::bpf_map_batch_opts {
+            sz: mem::size_of::<libbpf_sys::bpf_map_batch_opts>() as libbpf_sys::size_t,
+            elem_flags: flags.bits,
+            flags: 0,
+        };
+
+        let ret = unsafe {
+            libbpf_sys::bpf_map_update_batch(
+                self.fd,
+                key_buf.as_ptr() as *const c_void,
+                value_buf.as_ptr() as *const c_void,
+                &mut count,
+                &opts,
+            )
+        };
+
+        if ret != 0 {
+            Err(Error::System(-ret))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Deletes many keys in a single syscall.
+    ///
+    /// Each `keys[i]` must have exactly [`Map::key_size()`] elements.
+    pub fn delete_batch(&mut self, keys: &[Vec<u8>], flags: MapFlags) -> Result<()> {
+        let mut key_buf = Vec::with_capacity(keys.len() * self.key_size() as usize);
+        for key in keys {
+            if key.len() != self.key_size() as usize {
+                return Err(Error::InvalidInput(format!(
+                    "key_size {} != {}",
+                    key.len(),
+                    self.key_size()
+                )));
+            }
+            key_buf.extend_from_slice(key);
+        }
+
+        let mut count = keys.len() as u32;
+        let opts = libbpf_sys::bpf_map_batch_opts {
+            sz: mem::size_of::<libbpf_sys::bpf_map_batch_opts>() as libbpf_sys::size_t,
+            elem_flags: flags.bits,
+            flags: 0,
+        };
+
+        let ret = unsafe {
+            libbpf_sys::bpf_map_delete_batch(
+                self.fd,
+                key_buf.as_ptr() as *const c_void,
+                &mut count,
+                &opts,
+            )
+        };
+
+        if ret != 0 {
+            Err(Error::System(-ret))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Iterator over a [`Map`]'s keys, built on `bpf_map_get_next_key`.
+pub struct MapKeyIter<'a> {
+    map: &'a Map,
+    prev: Option<Vec<u8>>,
+    done: bool,
+}
+
+impl<'a> Iterator for MapKeyIter<'a> {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let mut next_key = vec![0u8; self.map.key_size() as usize];
+        let prev_ptr = match &self.prev {
+            Some(k) => k.as_ptr() as *const c_void,
+            None => std::ptr::null(),
+        };
+
+        let ret = unsafe {
+            libbpf_sys::bpf_map_get_next_key(
+                self.map.fd(),
+                prev_ptr,
+                next_key.as_mut_ptr() as *mut c_void,
+            )
+        };
+
+        if ret == 0 {
+            self.prev = Some(next_key.clone());
+            Some(Ok(next_key))
+        } else {
+            self.done = true;
+            let errno = errno::errno();
+            if errno::Errno::from_i32(errno) == errno::Errno::ENOENT {
+                None
+            } else {
+                Some(Err(Error::System(errno)))
+            }
+        }
+    }
+}
+
+/// Iterator over a [`Map`]'s key/value pairs, pairing each key from [`MapKeyIter`] with its
+/// current value.
+pub struct MapIter<'a> {
+    keys: MapKeyIter<'a>,
+}
+
+impl<'a> Iterator for MapIter<'a> {
+    type Item = Result<(Vec<u8>, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let key = match self.keys.next()? {
+                Ok(key) => key,
+                Err(e) => return Some(Err(e)),
+            };
+
+            match self.keys.map.lookup(&key, MapFlags::ANY) {
+                Ok(Some(value)) => return Some(Ok((key, value))),
+                // The key may have been deleted between get_next_key() and lookup(); skip it
+                // and move on to the next one rather than ending iteration early.
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Opaque cursor tracking progress through repeated [`Map::lookup_batch()`] calls.
+pub struct MapBatchCursor<'a> {
+    map: &'a Map,
+    batch: Vec<u8>,
+    started: bool,
+    done: bool,
+}
+
+impl<'a> MapBatchCursor<'a> {
+    /// Returns whether the map has been fully walked.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+}
+
+/// Returns the number of possible CPUs that this system may have. This is the number of CPU
+/// slots that per-CPU maps allocate, which may be larger than the number of CPUs actually
+/// online.
+pub fn num_possible_cpus() -> Result<usize> {
+    let ret = unsafe { libbpf_sys::libbpf_num_possible_cpus() };
+    if ret < 0 {
+        Err(Error::System(-ret))
+    } else {
+        Ok(ret as usize)
+    }
+}
+
+/// Rounds `n` up to the next multiple of `align`, matching how the kernel lays out per-CPU
+/// value slots.
+fn roundup(n: usize, align: usize) -> usize {
+    (n + align - 1) / align * align
 }
 
 #[rustfmt::skip]
@@ -331,8 +919,342 @@ pub enum MapType {
     SkStorage,
     DevmapHash,
     StructOps,
+    /// Not contiguous with the variants above because several map types the
+    /// kernel added in between (ring buffer, task/inode local storage, ...)
+    /// aren't represented here yet.
+    BloomFilter = 30,
     /// We choose to specify our own "unknown" type here b/c it's really up to the kernel
     /// to decide if it wants to reject the map. If it accepts it, it just means whoever
     /// using this library is a bit out of date.
     Unknown = u32::MAX,
 }
+
+impl MapType {
+    /// Returns whether this map type stores one value per possible CPU rather than one value
+    /// per key, meaning [`Map::lookup_percpu()`]/[`Map::update_percpu()`] must be used instead
+    /// of [`Map::lookup()`]/[`Map::update()`].
+    pub fn is_percpu(&self) -> bool {
+        matches!(
+            self,
+            MapType::PercpuHash
+                | MapType::PercpuArray
+                | MapType::LruPercpuHash
+                | MapType::PercpuCgroupStorage
+        )
+    }
+}
+
+/// Marker trait for plain-old-data types that can be copied to and from the
+/// raw byte buffers [`Map`] operates on.
+///
+/// # Safety
+///
+/// Implementors must not contain padding bytes that could expose
+/// uninitialized memory, pointers, or any other non-`'static`, non-POD state:
+/// the bytes of `Self` must be a valid, unique representation of its value.
+pub unsafe trait Pod: Copy {}
+
+macro_rules! impl_pod {
+    ($($t:ty),* $(,)?) => {
+        $(unsafe impl Pod for $t {})*
+    };
+}
+
+impl_pod!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, usize, isize, ());
+
+fn pod_to_bytes<T: Pod>(val: &T) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(val as *const T as *const u8, mem::size_of::<T>()) }
+}
+
+fn bytes_to_pod<T: Pod>(bytes: &[u8]) -> T {
+    debug_assert_eq!(bytes.len(), mem::size_of::<T>());
+    let mut val = MaybeUninit::<T>::uninit();
+    unsafe {
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), val.as_mut_ptr() as *mut u8, mem::size_of::<T>());
+        val.assume_init()
+    }
+}
+
+/// Checks that `map` is one of `expected_types` and that its key/value sizes
+/// match `K`/`V`, returning an error a typed wrapper's `TryFrom` can bubble up.
+fn validate_map_layout<K: Pod, V: Pod>(map: &Map, expected_types: &[MapType]) -> Result<()> {
+    if !expected_types.iter().any(|t| *t == map.map_type()) {
+        return Err(Error::InvalidInput(format!(
+            "map type {} is not supported by this typed wrapper",
+            map.map_type()
+        )));
+    }
+
+    if map.key_size() as usize != mem::size_of::<K>() {
+        return Err(Error::InvalidInput(format!(
+            "key_size {} != {}",
+            map.key_size(),
+            mem::size_of::<K>()
+        )));
+    }
+
+    if map.value_size() as usize != mem::size_of::<V>() {
+        return Err(Error::InvalidInput(format!(
+            "value_size {} != {}",
+            map.value_size(),
+            mem::size_of::<V>()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Typed view over a [`Hash`][MapType::Hash] or [`LruHash`][MapType::LruHash]
+/// [`Map`], trading the raw byte-slice API for `K`/`V` generic access.
+pub struct HashMap<'a, K, V> {
+    map: &'a Map,
+    _k: PhantomData<K>,
+    _v: PhantomData<V>,
+}
+
+impl<'a, K: Pod, V: Pod> TryFrom<&'a Map> for HashMap<'a, K, V> {
+    type Error = Error;
+
+    fn try_from(map: &'a Map) -> Result<Self> {
+        validate_map_layout::<K, V>(map, &[MapType::Hash, MapType::LruHash])?;
+        Ok(Self {
+            map,
+            _k: PhantomData,
+            _v: PhantomData,
+        })
+    }
+}
+
+impl<'a, K: Pod, V: Pod> TryFrom<&'a mut Map> for HashMap<'a, K, V> {
+    type Error = Error;
+
+    fn try_from(map: &'a mut Map) -> Result<Self> {
+        validate_map_layout::<K, V>(map, &[MapType::Hash, MapType::LruHash])?;
+        Ok(Self {
+            map,
+            _k: PhantomData,
+            _v: PhantomData,
+        })
+    }
+}
+
+impl<'a, K: Pod, V: Pod> HashMap<'a, K, V> {
+    /// Returns the value for `key`, or `None` if it isn't present.
+    pub fn get(&self, key: &K) -> Result<Option<V>> {
+        let val = self.map.lookup(pod_to_bytes(key), MapFlags::ANY)?;
+        Ok(val.map(|v| bytes_to_pod::<V>(&v)))
+    }
+
+    /// Inserts or updates the value stored at `key`.
+    pub fn insert(&self, key: &K, value: &V, flags: MapFlags) -> Result<()> {
+        self.map.update(pod_to_bytes(key), pod_to_bytes(value), flags)
+    }
+
+    /// Removes the value stored at `key`. Returns an error if `key` is not present.
+    pub fn remove(&self, key: &K) -> Result<()> {
+        self.map.delete(pod_to_bytes(key))
+    }
+}
+
+/// Typed view over an [`Array`][MapType::Array] [`Map`], indexed by `u32`.
+pub struct Array<'a, V> {
+    map: &'a Map,
+    _v: PhantomData<V>,
+}
+
+impl<'a, V: Pod> TryFrom<&'a Map> for Array<'a, V> {
+    type Error = Error;
+
+    fn try_from(map: &'a Map) -> Result<Self> {
+        validate_map_layout::<u32, V>(map, &[MapType::Array])?;
+        Ok(Self {
+            map,
+            _v: PhantomData,
+        })
+    }
+}
+
+impl<'a, V: Pod> TryFrom<&'a mut Map> for Array<'a, V> {
+    type Error = Error;
+
+    fn try_from(map: &'a mut Map) -> Result<Self> {
+        validate_map_layout::<u32, V>(map, &[MapType::Array])?;
+        Ok(Self {
+            map,
+            _v: PhantomData,
+        })
+    }
+}
+
+impl<'a, V: Pod> Array<'a, V> {
+    /// Returns the value at `index`.
+    pub fn get(&self, index: u32) -> Result<Option<V>> {
+        let val = self.map.lookup(pod_to_bytes(&index), MapFlags::ANY)?;
+        Ok(val.map(|v| bytes_to_pod::<V>(&v)))
+    }
+
+    /// Overwrites the value at `index`. Array maps are pre-allocated and fixed
+    /// size, so this never inserts a new slot.
+    pub fn insert(&self, index: u32, value: &V, flags: MapFlags) -> Result<()> {
+        self.map.update(pod_to_bytes(&index), pod_to_bytes(value), flags)
+    }
+}
+
+/// Typed FIFO view over a [`Queue`][MapType::Queue] [`Map`].
+pub struct Queue<'a, V> {
+    map: &'a Map,
+    _v: PhantomData<V>,
+}
+
+impl<'a, V: Pod> TryFrom<&'a Map> for Queue<'a, V> {
+    type Error = Error;
+
+    fn try_from(map: &'a Map) -> Result<Self> {
+        validate_map_layout::<(), V>(map, &[MapType::Queue])?;
+        Ok(Self {
+            map,
+            _v: PhantomData,
+        })
+    }
+}
+
+impl<'a, V: Pod> TryFrom<&'a mut Map> for Queue<'a, V> {
+    type Error = Error;
+
+    fn try_from(map: &'a mut Map) -> Result<Self> {
+        validate_map_layout::<(), V>(map, &[MapType::Queue])?;
+        Ok(Self {
+            map,
+            _v: PhantomData,
+        })
+    }
+}
+
+impl<'a, V: Pod> Queue<'a, V> {
+    /// Pushes `value` onto the queue.
+    pub fn push(&self, value: &V, flags: MapFlags) -> Result<()> {
+        self.map.update(&[], pod_to_bytes(value), flags)
+    }
+
+    /// Pops the oldest value off the queue, or returns `None` if it's empty.
+    pub fn pop(&self) -> Result<Option<V>> {
+        let out = self.map.lookup_and_delete(&[])?;
+        Ok(out.map(|v| bytes_to_pod::<V>(&v)))
+    }
+}
+
+/// Typed LIFO view over a [`Stack`][MapType::Stack] [`Map`].
+///
+/// Identical in shape to [`Queue`]; the FIFO/LIFO ordering is entirely a
+/// kernel-side property of the underlying map type.
+pub struct Stack<'a, V> {
+    map: &'a Map,
+    _v: PhantomData<V>,
+}
+
+impl<'a, V: Pod> TryFrom<&'a Map> for Stack<'a, V> {
+    type Error = Error;
+
+    fn try_from(map: &'a Map) -> Result<Self> {
+        validate_map_layout::<(), V>(map, &[MapType::Stack])?;
+        Ok(Self {
+            map,
+            _v: PhantomData,
+        })
+    }
+}
+
+impl<'a, V: Pod> TryFrom<&'a mut Map> for Stack<'a, V> {
+    type Error = Error;
+
+    fn try_from(map: &'a mut Map) -> Result<Self> {
+        validate_map_layout::<(), V>(map, &[MapType::Stack])?;
+        Ok(Self {
+            map,
+            _v: PhantomData,
+        })
+    }
+}
+
+impl<'a, V: Pod> Stack<'a, V> {
+    /// Pushes `value` onto the stack.
+    pub fn push(&self, value: &V, flags: MapFlags) -> Result<()> {
+        self.map.update(&[], pod_to_bytes(value), flags)
+    }
+
+    /// Pops the most recently pushed value off the stack, or returns `None`
+    /// if it's empty.
+    pub fn pop(&self) -> Result<Option<V>> {
+        let out = self.map.lookup_and_delete(&[])?;
+        Ok(out.map(|v| bytes_to_pod::<V>(&v)))
+    }
+}
+
+/// Typed view over a [`BloomFilter`][MapType::BloomFilter] [`Map`].
+///
+/// Bloom filters have no keys: membership is tested and added purely by
+/// value.
+pub struct BloomFilter<'a, V> {
+    map: &'a Map,
+    _v: PhantomData<V>,
+}
+
+impl<'a, V: Pod> TryFrom<&'a Map> for BloomFilter<'a, V> {
+    type Error = Error;
+
+    fn try_from(map: &'a Map) -> Result<Self> {
+        validate_map_layout::<(), V>(map, &[MapType::BloomFilter])?;
+        Ok(Self {
+            map,
+            _v: PhantomData,
+        })
+    }
+}
+
+impl<'a, V: Pod> TryFrom<&'a mut Map> for BloomFilter<'a, V> {
+    type Error = Error;
+
+    fn try_from(map: &'a mut Map) -> Result<Self> {
+        validate_map_layout::<(), V>(map, &[MapType::BloomFilter])?;
+        Ok(Self {
+            map,
+            _v: PhantomData,
+        })
+    }
+}
+
+impl<'a, V: Pod> BloomFilter<'a, V> {
+    /// Returns whether `value` may be a member of the filter. A `false`
+    /// result is authoritative; a `true` result may be a false positive.
+    pub fn contains(&self, value: &V) -> Result<bool> {
+        // Bloom filters have no keys (key_size() == 0) and the kernel reads the value to test
+        // from the value slot, same as insert() below. Map::lookup() hands the kernel an empty
+        // output buffer instead, so it can't be used here; the key pointer is NULL.
+        let mut probe = pod_to_bytes(value).to_vec();
+
+        let ret = unsafe {
+            libbpf_sys::bpf_map_lookup_elem_flags(
+                self.map.fd(),
+                std::ptr::null(),
+                probe.as_mut_ptr() as *mut c_void,
+                MapFlags::ANY.bits,
+            )
+        };
+
+        if ret == 0 {
+            Ok(true)
+        } else {
+            let errno = errno::errno();
+            if errno::Errno::from_i32(errno) == errno::Errno::ENOENT {
+                Ok(false)
+            } else {
+                Err(Error::System(errno))
+            }
+        }
+    }
+
+    /// Adds `value` to the filter.
+    pub fn insert(&self, value: &V, flags: MapFlags) -> Result<()> {
+        self.map.update(&[], pod_to_bytes(value), flags)
+    }
+}